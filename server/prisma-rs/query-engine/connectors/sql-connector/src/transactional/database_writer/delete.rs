@@ -1,32 +1,104 @@
 use crate::{
     error::SqlError,
     transaction_ext,
-    write_query::{DeleteActions, NestedActions, WriteQueryBuilder},
+    write_query::{DeleteActions, NestedActions, RelationIntegrity, WriteQueryBuilder},
 };
-use connector_interface::{error::RecordFinderInfo, filter::RecordFinder};
-use prisma_models::{GraphqlId, RelationFieldRef, SingleRecord};
-use prisma_query::connector::{Transaction, Queryable};
+use connector_interface::{
+    error::RecordFinderInfo,
+    filter::{Filter, RecordFinder},
+};
+use prisma_models::{GraphqlId, OnDelete, Record, RelationFieldRef, SelectedFields, SingleRecord};
+use prisma_query::connector::{ConnectorCapability, Transaction, Queryable};
+use std::collections::HashSet;
 use std::sync::Arc;
 
 /// A top level delete that removes one record. Violating any relations or a
 /// non-existing record will cause an error.
 ///
+/// `integrity` picks whether referential integrity is enforced by the
+/// underlying database (in which case a `NoAction` relation is left alone
+/// here) or emulated by the connector (in which case `NoAction` is treated
+/// the same as `Restrict`, blocking the delete).
+///
+/// The delete re-applies the full predicate of `record_finder`, not just
+/// the resolved id, so an optimistic-concurrency filter (e.g. a `version`
+/// or `updatedAt` equality) that the caller folded into the finder still
+/// holds at delete time. If the row was concurrently changed or removed in
+/// between, zero rows match and this returns
+/// `SqlError::RecordNotFound` instead of silently deleting nothing.
+///
+/// On connectors that support a `DELETE ... RETURNING` clause
+/// (Postgres, SQLite) the record is fetched as a side effect of the
+/// delete itself, avoiding the extra `SELECT` round trip. Connectors
+/// without that capability fall back to the previous find-then-delete
+/// flow.
+///
 /// Will return the deleted record if the delete was successful.
-pub fn execute(conn: &mut Transaction, record_finder: &RecordFinder) -> crate::Result<SingleRecord> {
+pub fn execute(
+    conn: &mut Transaction,
+    record_finder: &RecordFinder,
+    integrity: RelationIntegrity,
+) -> crate::Result<SingleRecord> {
     let model = record_finder.field.model();
-    let record = transaction_ext::find_record(conn, record_finder)?;
-    let id = record.get_id_value(Arc::clone(&model)).unwrap();
+    let id = transaction_ext::find_id(conn, record_finder)?;
 
-    DeleteActions::check_relation_violations(Arc::clone(&model), &[&id], |select| {
+    DeleteActions::check_relation_violations(Arc::clone(&model), &[&id], integrity, |select| {
         let ids = transaction_ext::select_ids(conn, select)?;
         Ok(ids.into_iter().next())
     })?;
 
-    for delete in WriteQueryBuilder::delete_many(model, &[&id]) {
-        conn.delete(delete)?;
+    let mut visited = HashSet::new();
+    visited.insert((model.name.clone(), id.clone()));
+    apply_nested_delete_actions(conn, Arc::clone(&model), &[id.clone()], integrity, &mut visited)?;
+
+    let filter = Filter::from(record_finder).and(Filter::from(id.clone()));
+
+    if conn.capabilities().contains(ConnectorCapability::ReturningClause) {
+        let selected_fields = SelectedFields::from(Arc::clone(&model));
+        let mut rows = conn.delete_returning(WriteQueryBuilder::delete_many_matching_returning(
+            Arc::clone(&model),
+            &[&id],
+            filter,
+            &selected_fields,
+        ))?;
+
+        match rows.pop() {
+            Some(row) => Ok(SingleRecord {
+                record: Record::from(row),
+                field_names: selected_fields.names(),
+            }),
+            None => Err(SqlError::RecordNotFound(RecordFinderInfo::for_id(model, &id))),
+        }
+    } else {
+        let record = transaction_ext::find_record(conn, record_finder)?;
+        let deleted = delete_matching(conn, Arc::clone(&model), &id, filter)?;
+
+        if deleted == 0 {
+            return Err(SqlError::RecordNotFound(RecordFinderInfo::for_id(model, &id)));
+        }
+
+        Ok(record)
     }
+}
 
-    Ok(record)
+/// Issues `WriteQueryBuilder::delete_many_matching` for `id` AND'd with
+/// `filter`, returning how many rows the database actually removed. A
+/// result of `0` means the row no longer satisfied `filter` by the time
+/// the delete ran — either it was already gone or a concurrent write
+/// changed the column the caller is optimistically locking on.
+fn delete_matching(
+    conn: &mut Transaction,
+    model: prisma_models::ModelRef,
+    id: &GraphqlId,
+    filter: Filter,
+) -> crate::Result<u64> {
+    let mut rows_affected = 0;
+
+    for delete in WriteQueryBuilder::delete_many_matching(model, &[id], filter) {
+        rows_affected += conn.execute(delete)?;
+    }
+
+    Ok(rows_affected)
 }
 
 /// A nested delete that removes one item related to the given `parent_id`.
@@ -38,12 +110,24 @@ pub fn execute(conn: &mut Transaction, record_finder: &RecordFinder) -> crate::R
 /// - Violating any relations where the deleted record is required
 /// - If the deleted record is not connected to the parent
 /// - The record does not exist
+///
+/// `NestedActions` requires `Send + Sync` on the trait itself (see
+/// `write_query`), so `&dyn NestedActions` — and the closures this
+/// function builds around `check_relation_violations`/`ensure_connected`
+/// — can be carried across an `.await` point by an async, multi-threaded
+/// `with_transaction` executor.
+///
+/// As in `execute`, when `record_finder` carries more than an id (e.g. an
+/// optimistic-concurrency predicate), that full predicate is re-checked at
+/// delete time and `SqlError::RecordNotFound` is returned if it no longer
+/// matches.
 pub fn execute_nested(
     conn: &mut Transaction,
     parent_id: &GraphqlId,
-    actions: &NestedActions,
+    actions: &dyn NestedActions,
     record_finder: &Option<RecordFinder>,
     relation_field: RelationFieldRef,
+    integrity: RelationIntegrity,
 ) -> crate::Result<()> {
     if let Some(ref record_finder) = record_finder {
         transaction_ext::find_id(conn, record_finder)?;
@@ -85,14 +169,265 @@ pub fn execute_nested(
 
     let related_model = relation_field.related_model();
 
-    DeleteActions::check_relation_violations(related_model, &[&child_id; 1], |select| {
+    DeleteActions::check_relation_violations(Arc::clone(&related_model), &[&child_id; 1], integrity, |select| {
+        let ids = transaction_ext::select_ids(conn, select)?;
+        Ok(ids.into_iter().next())
+    })?;
+
+    let mut visited = HashSet::new();
+    visited.insert((related_model.name.clone(), child_id.clone()));
+    apply_nested_delete_actions(conn, Arc::clone(&related_model), &[child_id.clone()], integrity, &mut visited)?;
+
+    let filter = match record_finder {
+        Some(record_finder) => Filter::from(record_finder).and(Filter::from(child_id.clone())),
+        None => Filter::from(child_id.clone()),
+    };
+
+    let deleted = delete_matching(conn, Arc::clone(&related_model), &child_id, filter)?;
+
+    if deleted == 0 {
+        return Err(SqlError::RecordNotFound(RecordFinderInfo::for_id(related_model, &child_id)));
+    }
+
+    Ok(())
+}
+
+/// The number of ids a single `DELETE ... WHERE id IN (...)` statement is
+/// allowed to carry before it gets split into multiple statements. Keeps
+/// us well under the bind parameter limits of the supported connectors.
+const PARAMETER_LIMIT: usize = 500;
+
+/// Top level `deleteMany`: removes every record of `model` matching
+/// `filter`, honoring the same emulated referential actions and
+/// `RelationIntegrity` mode as a single-record delete, and returns the
+/// number of rows removed.
+pub fn execute_many(
+    conn: &mut Transaction,
+    model: prisma_models::ModelRef,
+    filter: &Filter,
+    integrity: RelationIntegrity,
+) -> crate::Result<usize> {
+    let ids = transaction_ext::select_ids(conn, WriteQueryBuilder::select_ids(Arc::clone(&model), filter.clone()))?;
+
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let id_refs: Vec<&GraphqlId> = ids.iter().collect();
+
+    DeleteActions::check_relation_violations(Arc::clone(&model), &id_refs, integrity, |select| {
+        let ids = transaction_ext::select_ids(conn, select)?;
+        Ok(ids.into_iter().next())
+    })?;
+
+    let mut visited = HashSet::new();
+    visited.extend(ids.iter().map(|id| (model.name.clone(), id.clone())));
+    apply_nested_delete_actions(conn, Arc::clone(&model), &ids, integrity, &mut visited)?;
+
+    let mut rows_affected = 0;
+
+    for chunk in ids.chunks(PARAMETER_LIMIT) {
+        let chunk_refs: Vec<&GraphqlId> = chunk.iter().collect();
+
+        for delete in WriteQueryBuilder::delete_many(Arc::clone(&model), &chunk_refs) {
+            rows_affected += conn.execute(delete)?;
+        }
+    }
+
+    Ok(rows_affected as usize)
+}
+
+/// Nested `deleteMany`: removes every record related to `parent_id`
+/// through `relation_field` that also matches `filter`, returning the
+/// number of rows removed. Unlike `execute_nested`, a `filter` that
+/// matches nothing is not an error — it simply deletes zero rows.
+pub fn execute_nested_many(
+    conn: &mut Transaction,
+    parent_id: &GraphqlId,
+    relation_field: RelationFieldRef,
+    filter: &Filter,
+    integrity: RelationIntegrity,
+) -> crate::Result<usize> {
+    let related_model = relation_field.related_model();
+
+    let scoped_select = WriteQueryBuilder::select_ids(
+        Arc::clone(&related_model),
+        filter.clone().and(relation_field.scoped_to_parent(parent_id)),
+    );
+
+    let ids = transaction_ext::select_ids(conn, scoped_select)?;
+
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let id_refs: Vec<&GraphqlId> = ids.iter().collect();
+
+    DeleteActions::check_relation_violations(Arc::clone(&related_model), &id_refs, integrity, |select| {
         let ids = transaction_ext::select_ids(conn, select)?;
         Ok(ids.into_iter().next())
     })?;
 
-    for delete in WriteQueryBuilder::delete_many(relation_field.related_model(), &[&child_id]) {
-        conn.delete(delete)?;
+    let mut visited = HashSet::new();
+    visited.extend(ids.iter().map(|id| (related_model.name.clone(), id.clone())));
+    apply_nested_delete_actions(conn, Arc::clone(&related_model), &ids, integrity, &mut visited)?;
+
+    let mut rows_affected = 0;
+
+    for chunk in ids.chunks(PARAMETER_LIMIT) {
+        let chunk_refs: Vec<&GraphqlId> = chunk.iter().collect();
+
+        for delete in WriteQueryBuilder::delete_many(Arc::clone(&related_model), &chunk_refs) {
+            rows_affected += conn.execute(delete)?;
+        }
+    }
+
+    Ok(rows_affected as usize)
+}
+
+/// Walks every relation field of `model` and emulates the configured
+/// `onDelete` action for the records identified by `ids`, before those
+/// records themselves are deleted.
+///
+/// `Cascade` recurses depth-first into the children (so grandchildren get
+/// their own actions applied before the child row disappears), `SetNull`
+/// and `SetDefault` null out or reset the inlined foreign key scalars on
+/// the child side, and `Restrict`/`NoAction` are left to
+/// `DeleteActions::check_relation_violations`.
+///
+/// That check is only rerun for a `Cascade` child, right before it is
+/// itself recursed into/deleted — a `Cascade` descendant can have its own
+/// `Restrict` children (`A --Cascade--> B --Restrict--> C`), and the
+/// caller only ever checked the root once before calling this function.
+/// It must NOT run for `SetNull`/`SetDefault`: those children are never
+/// deleted, only updated, so a `Restrict` relation further down from them
+/// (e.g. `Author --SetNull--> Post --Restrict--> Comment`) is irrelevant
+/// to this delete and must not block it.
+///
+/// `visited` guards against self-referencing or circular relation graphs:
+/// a `(model, id)` pair is only ever processed once per top-level delete.
+/// Callers seed it with the root ids so a cascade that loops back to one
+/// of them is skipped rather than deleted twice.
+fn apply_nested_delete_actions(
+    conn: &mut Transaction,
+    model: prisma_models::ModelRef,
+    ids: &[GraphqlId],
+    integrity: RelationIntegrity,
+    visited: &mut HashSet<(String, GraphqlId)>,
+) -> crate::Result<()> {
+    for relation_field in model.fields().relation() {
+        let child_model = relation_field.related_model();
+        let child_ids = transaction_ext::select_ids(conn, relation_field.child_ids_select(ids))?;
+        let child_ids = unvisited_ids(&child_model.name, child_ids, visited);
+
+        if child_ids.is_empty() {
+            continue;
+        }
+
+        let child_id_refs: Vec<&GraphqlId> = child_ids.iter().collect();
+        let on_delete = relation_field.on_delete();
+
+        if requires_violation_recheck(on_delete) {
+            DeleteActions::check_relation_violations(Arc::clone(&child_model), &child_id_refs, integrity, |select| {
+                let ids = transaction_ext::select_ids(conn, select)?;
+                Ok(ids.into_iter().next())
+            })?;
+        }
+
+        match on_delete {
+            OnDelete::Cascade => {
+                apply_nested_delete_actions(conn, Arc::clone(&child_model), &child_ids, integrity, visited)?;
+
+                for delete in WriteQueryBuilder::delete_many(child_model, &child_id_refs) {
+                    conn.delete(delete)?;
+                }
+            }
+            OnDelete::SetNull => {
+                for update in WriteQueryBuilder::set_null_fks(Arc::clone(&child_model), &relation_field, &child_id_refs) {
+                    conn.update(update)?;
+                }
+            }
+            OnDelete::SetDefault => {
+                for update in WriteQueryBuilder::set_default_fks(Arc::clone(&child_model), &relation_field, &child_id_refs) {
+                    conn.update(update)?;
+                }
+            }
+            OnDelete::Restrict | OnDelete::NoAction => (),
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Whether `apply_nested_delete_actions` must rerun
+/// `DeleteActions::check_relation_violations` for a child before acting
+/// on it: only true for `Cascade`, whose children are themselves about to
+/// be deleted and so need their own `Restrict`/`NoAction` relations
+/// checked. `SetNull`/`SetDefault` children are only updated, never
+/// deleted, so a `Restrict` relation further down the graph from them
+/// does not apply to this delete.
+fn requires_violation_recheck(on_delete: OnDelete) -> bool {
+    matches!(on_delete, OnDelete::Cascade)
+}
+
+/// Keeps only the ids in `candidate_ids` not already marked visited for
+/// `model_name`, marking each kept id as visited as it goes. Split out of
+/// `apply_nested_delete_actions` so the cycle guard can be unit tested
+/// without a `Transaction`.
+fn unvisited_ids(
+    model_name: &str,
+    candidate_ids: Vec<GraphqlId>,
+    visited: &mut HashSet<(String, GraphqlId)>,
+) -> Vec<GraphqlId> {
+    candidate_ids
+        .into_iter()
+        .filter(|id| visited.insert((model_name.to_string(), id.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> GraphqlId {
+        GraphqlId::from(s)
+    }
+
+    #[test]
+    fn unvisited_ids_drops_ids_already_seen_for_the_same_model() {
+        let mut visited = HashSet::new();
+        visited.insert(("Post".to_string(), id("1")));
+
+        let kept = unvisited_ids("Post", vec![id("1"), id("2")], &mut visited);
+
+        assert_eq!(kept, vec![id("2")]);
+    }
+
+    #[test]
+    fn unvisited_ids_keeps_the_same_id_for_a_different_model() {
+        let mut visited = HashSet::new();
+        visited.insert(("Post".to_string(), id("1")));
+
+        let kept = unvisited_ids("Comment", vec![id("1")], &mut visited);
+
+        assert_eq!(kept, vec![id("1")]);
+    }
+
+    #[test]
+    fn unvisited_ids_deduplicates_within_a_single_call() {
+        let mut visited = HashSet::new();
+
+        let kept = unvisited_ids("Post", vec![id("1"), id("1")], &mut visited);
+
+        assert_eq!(kept, vec![id("1")]);
+    }
+
+    #[test]
+    fn only_cascade_requires_rechecking_relation_violations_on_the_child() {
+        assert!(requires_violation_recheck(OnDelete::Cascade));
+        assert!(!requires_violation_recheck(OnDelete::SetNull));
+        assert!(!requires_violation_recheck(OnDelete::SetDefault));
+        assert!(!requires_violation_recheck(OnDelete::Restrict));
+        assert!(!requires_violation_recheck(OnDelete::NoAction));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::error::SqlError;
+use prisma_models::{GraphqlId, ModelRef, OnDelete};
+use prisma_query::ast::Select;
+
+/// Whether referential integrity for a delete is enforced by the
+/// underlying database or has to be emulated by this connector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationIntegrity {
+    /// The database enforces its own foreign keys; `NoAction` is left
+    /// alone and handled entirely by the engine.
+    DatabaseEnforced,
+    /// No database-level enforcement; `NoAction` is treated as an alias
+    /// of `Restrict`.
+    Emulated,
+}
+
+/// Resolves whether a record nested under a parent is actually connected
+/// to it, for the domain checks a nested write has to run before it's
+/// allowed to proceed.
+///
+/// `Send + Sync` so a `&dyn NestedActions` can be captured by a closure
+/// that crosses an `.await` point inside a multi-threaded
+/// `with_transaction` executor; implementors must avoid `Rc` or other
+/// non-`Sync` interior state to satisfy this.
+pub trait NestedActions: Send + Sync {
+    /// Returns a `Select` that yields a row iff `child_id` is connected
+    /// to `parent_id`, paired with a closure turning that into a
+    /// `Result` the caller can propagate with `?`.
+    fn ensure_connected(
+        &self,
+        parent_id: &GraphqlId,
+        child_id: &GraphqlId,
+    ) -> (Select, Box<dyn Fn(bool) -> crate::Result<()> + Send + Sync>);
+}
+
+/// Relation-violation checks shared by every delete path.
+pub struct DeleteActions;
+
+impl DeleteActions {
+    /// Checks whether deleting `ids` from `model` would violate a
+    /// `Restrict` relation — or, under `RelationIntegrity::Emulated`, a
+    /// `NoAction` one, which is otherwise left to the database.
+    ///
+    /// `resolve` runs the generated `Select` against the open
+    /// transaction and returns the first matching id, if any. It takes
+    /// `FnMut` rather than `Fn` so callers can thread a `&mut Transaction`
+    /// through it, and it must be `Send` so this can be called from
+    /// inside an async, multi-threaded `with_transaction` closure.
+    pub fn check_relation_violations<F>(
+        model: ModelRef,
+        ids: &[&GraphqlId],
+        integrity: RelationIntegrity,
+        mut resolve: F,
+    ) -> crate::Result<()>
+    where
+        F: FnMut(Select) -> crate::Result<Option<GraphqlId>> + Send,
+    {
+        for relation_field in model.fields().relation() {
+            let blocks = match (relation_field.on_delete(), integrity) {
+                (OnDelete::Restrict, _) => true,
+                (OnDelete::NoAction, RelationIntegrity::Emulated) => true,
+                _ => false,
+            };
+
+            if !blocks {
+                continue;
+            }
+
+            if resolve(relation_field.child_ids_select(ids))?.is_some() {
+                return Err(SqlError::RelationViolation {
+                    relation_name: relation_field.relation().name.clone(),
+                    model_a_name: model.name.clone(),
+                    model_b_name: relation_field.related_model().name.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}